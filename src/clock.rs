@@ -5,6 +5,19 @@
 use core::cmp;
 use core::ops::{Div, Mul};
 use core::time::Duration;
+use cortex_m::peripheral::SYST;
+
+use crate::timer::TimerError;
+
+/// Provide the [`Frequency`] feeding a SysTick-backed peripheral.
+///
+/// Implemented by the chip's clock tree so that SysTick-based peripherals,
+/// such as [`crate::delay::SysTickDelay`], can ask at what rate they are
+/// actually counting.
+pub trait Clocks {
+    /// Return the frequency driving `syst`.
+    fn get_syst_clock(&self, syst: &mut SYST) -> Frequency;
+}
 
 /// Represent frequency range magnitude
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -90,6 +103,40 @@ impl Frequency {
     into_x!(into_mega, MegaHertz);
     into_x!(into_milli, MilliHertz);
 
+    /// Pick the `(prescaler, reload)` pair that realizes `target` as
+    /// closely as possible on a count-down timer whose counter is
+    /// `counter_bits` wide, searching prescalers `1..=max_prescaler`.
+    ///
+    /// Returns `Err(TimerError::Unreachable)` if `target` cannot be reached
+    /// even when dividing by `max_prescaler`.
+    pub fn reload_for(&self, target: Duration, counter_bits: u32, max_prescaler: u32) -> Result<(u32, u32), TimerError> {
+        let needed = self.ticks_in(target);
+        // Inclusive bound: a `counter_bits`-wide reload register can only
+        // ever hold up to `2^counter_bits - 1`.
+        let max_count = (1u64 << counter_bits) - 1;
+
+        if needed <= max_count {
+            return Ok((1, needed as u32));
+        }
+
+        let mut best: Option<(u32, u32, u64)> = None;
+        for p in 1..=max_prescaler {
+            let reload = needed / p as u64;
+            if reload == 0 || reload > max_count {
+                continue;
+            }
+            let error = needed - p as u64 * reload;
+            if best.map_or(true, |(_, _, best_error)| error < best_error) {
+                best = Some((p, reload as u32, error));
+            }
+            if error == 0 {
+                break;
+            }
+        }
+
+        best.map(|(p, reload, _)| (p, reload)).ok_or(TimerError::Unreachable)
+    }
+
 }
 
 impl Div<u32> for Frequency {
@@ -191,7 +238,7 @@ impl U32Ext for u32 {
 mod test {
 
     #[allow(unused_imports)]
-    use super::{FreqRange, U32Ext};
+    use super::{FreqRange, TimerError, U32Ext};
 
     #[test]
     fn multiply() {
@@ -242,6 +289,29 @@ mod test {
         assert_eq!(0.clamp(1, 3), 1);
     }
 
+    #[test]
+    fn reload_for_fits_directly() {
+        assert_eq!(1.khz().reload_for(1.s(), 16, 8), Ok((1, 1_000)));
+    }
+
+    #[test]
+    fn reload_for_needs_prescaler() {
+        assert_eq!(1.mhz().reload_for(1.s(), 16, 32), Ok((16, 62_500)));
+    }
+
+    #[test]
+    fn reload_for_unreachable() {
+        assert_eq!(1.mhz().reload_for(10.s(), 16, 8), Err(TimerError::Unreachable));
+    }
+
+    #[test]
+    fn reload_for_rejects_reload_one_past_the_counter_width() {
+        // A 16-bit reload register can only ever hold 0..=65535, so a
+        // target landing exactly on 65536 ticks must not "fit directly".
+        assert_eq!(65_536.hz().reload_for(1.s(), 16, 1), Err(TimerError::Unreachable));
+        assert_eq!(65_536.hz().reload_for(1.s(), 16, 2), Ok((2, 32_768)));
+    }
+
     #[test]
     fn comp() {
         assert!(1.mhz() < 2000.khz());
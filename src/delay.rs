@@ -1,109 +1,216 @@
-//! Implement delay abstraction.
+//! Implement delay abstraction backed by the Cortex-M SysTick peripheral.
 
-#![allow(missing_docs)]
-use crate::peripheral::SYST;
-use crate::peripheral::syst::SystClkSource;
 use crate::clock::Clocks;
+use crate::timer::{Alarm, Counter, Ticks24, Timer, TimerInstant};
 use core::time::Duration;
-use core::cmp::min;
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::SYST;
 
-/// A `Timer`` trait to represent count down time.
-/// This is a typical peripheral that has an internal counter that decrease or increase over time until it reach 0.
-pub trait Timer {
+/// SysTick counts down from its reload value; this is the widest value it
+/// can hold, since the counter itself is only 24 bits wide.
+pub(crate) const MAX_RVR: u32 = 0x00FF_FFFF;
 
-    /// Size of the counter
-    type Size
+/// Delay based on the Cortex-M SysTick timer.
+pub struct SysTickDelay<T>
+where
+    T: Clocks,
+{
+    syst: SYST,
+    clocks: T,
+    /// The `(reference, dt)` pair armed through [`Alarm::set_alarm`], if
+    /// any. Polled against [`Counter::get_ticks`] by the caller; SysTick has
+    /// no independent compare register of its own to fire this on its
+    /// behalf.
+    alarm: (Ticks24, Ticks24),
+}
+
+impl<T> SysTickDelay<T>
+where
+    T: Clocks,
+{
+    /// Build a new SysTick timer base on external source clock.
+    /// External clock is vendor dependent.
+    pub fn new_external(mut syst: SYST, clocks: T) -> Self {
+        syst.set_clock_source(SystClkSource::External);
+        syst.set_reload(MAX_RVR);
+        SysTickDelay {
+            syst,
+            clocks,
+            alarm: (Ticks24(0), Ticks24(0)),
+        }
+    }
 
-    /// Pause the execution for Duration.
-    fn delay(&mut self, d: Duration);
+    /// Start the timer and capture the current instant.
+    pub fn start(self) -> TimerInstant<Self> {
+        Timer::start(self)
+    }
+
+    /// Stop the counting timer.
+    pub fn stop(self) -> Self {
+        Timer::stop(self)
+    }
 
-    /// Pause execution assuming interrupt is enabled
-    /// and correctly handler.
-    fn delay_with_interrupt(&mut self, d: Duration) {
-        // By default is a not optimal delay.
-        self.delay(d);
+    /// Pause the execution for `d`, chunking the wait into successive
+    /// 24-bit reload windows when `d` exceeds what the SysTick counter can
+    /// hold in a single pass.
+    pub fn delay(&mut self, d: Duration) {
+        Timer::delay(self, d)
     }
 
-    /// Start a timer from a delay counter
-    fn start(mut self) ->  TimerInstant<Self>;
+    /// Return the duration of a single SysTick clock pulse.
+    pub fn tick(&mut self) -> Duration {
+        Timer::tick(self)
+    }
 
+    /// Test if the counter has wrapped to its initial value.
+    pub fn has_wrapped(&mut self) -> bool {
+        Timer::has_wrapped(self)
+    }
 
-    fn has_wrapped(&mut self) -> bool;
-    fn get_current(&mut self) -> Self::Size;
+    /// Return the current counter value.
+    pub fn get_current(&mut self) -> u32 {
+        Timer::get_current(self)
+    }
 
-/// Capture an instant from a delay.
-pub struct TimerInstant<T>
-where T : Delay
-{
-    delay: TDelay,
+    /// Split this delay back into its raw `SYST` peripheral and clock
+    /// source, for use by types built on top of it (e.g. the monotonic
+    /// clock in [`crate::monotonic`]).
+    pub(crate) fn free(self) -> (SYST, T) {
+        (self.syst, self.clocks)
+    }
 }
 
-impl<T> SysTickInstant<T>
-where T : Clocks
+impl<T> Timer for SysTickDelay<T>
+where
+    T: Clocks,
 {
-    fn now(delay: SysTickDelay<T>) -> Self {
-        SysTickInstant {
-            delay,
+    type U = u32;
+
+    /// A zero-tick remainder is never armed, since a reload of 0 never sets
+    /// COUNTFLAG (it only sets on a 1->0 transition).
+    fn delay(&mut self, d: Duration) {
+        let mut total = self.clocks.get_syst_clock(&mut self.syst).ticks_in(d);
+        while total > 0 {
+            let current_rvr = core::cmp::min(total, MAX_RVR as u64) as u32;
+            self.syst.set_reload(current_rvr);
+            self.syst.clear_current();
+            self.syst.enable_counter();
+            while !self.syst.has_wrapped() {}
+            self.syst.disable_counter();
+            total -= current_rvr as u64;
         }
     }
 
-    pub fn elapsed(&mut self) -> Duration {
-        if self.delay.has_wrapped() {
-            panic!("Can not tell the elapse time as we have wrapped.")
+    /// Arms the counter for `d` on the first poll (if it is not already
+    /// running) and reports completion once it has wrapped. Unlike
+    /// `delay`, a single `wait` does not itself chunk a `d` wider than one
+    /// 24-bit reload window; callers wanting that should drive this timer
+    /// through [`crate::timer::CountDownTimer`] instead, which re-arms via
+    /// `set_period` across successive windows.
+    fn wait(&mut self, d: Duration) -> nb::Result<(), void::Void> {
+        if !self.syst.is_counter_enabled() {
+            self.set_period(d);
+            self.syst.enable_counter();
+            return Err(nb::Error::WouldBlock);
+        }
+
+        if self.syst.has_wrapped() {
+            self.syst.disable_counter();
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
         }
-        self.delay.tick() * (0x0FF_FFFF - self.delay.get_current())
     }
 
-    pub fn stop(self) -> SysTickDelay<T> {
-        self.delay.stop()
+    fn start(mut self) -> TimerInstant<Self> {
+        self.syst.clear_current();
+        self.syst.enable_counter();
+        TimerInstant::now(self)
     }
-}
 
-/// Delay base on Systick.
-pub struct SysTickDelay<T>
-where T : Clocks
-{
-    syst: SYST,
-    clocks: T,
+    fn stop(mut self) -> Self {
+        self.syst.disable_counter();
+        self
+    }
+
+    fn has_wrapped(&mut self) -> bool {
+        self.syst.has_wrapped()
+    }
+
+    fn limit_value(&self) -> u32 {
+        MAX_RVR
+    }
+
+    fn get_current(&mut self) -> u32 {
+        SYST::get_current()
+    }
+
+    fn tick(&mut self) -> Duration {
+        self.clocks.get_syst_clock(&mut self.syst).tick()
+    }
+
+    fn set_period(&mut self, d: Duration) {
+        let ticks = self.clocks.get_syst_clock(&mut self.syst).ticks_in(d);
+        let reload = core::cmp::min(ticks, MAX_RVR as u64) as u32;
+        self.syst.set_reload(reload);
+        self.syst.clear_current();
+    }
 }
 
-/// Delay using the SysTick timer
-impl<T> SysTickDelay<T>
+impl<T> Counter for SysTickDelay<T>
 where
-    T: Clocks
+    T: Clocks,
 {
+    /// `Ticks24`, not a plain `u32`: SysTick's counter is only 24 bits
+    /// wide, so `Alarm::has_expired`'s wrapping arithmetic needs to wrap at
+    /// `2^24`, not at the full 32-bit range a bare `u32` would assume.
+    type U = Ticks24;
 
-    /// Build a new SysTick timer base on external source clock.
-    /// External clock is vendor dependent
-    pub fn new_external(mut syst: SYST, clocks: T) -> Self {
-        syst.set_clock_source(SystClkSource::External);
-        SysTickDelay {
-            syst,
-            clocks
-        }
-    }
-
-    pub fn start(mut self) ->  SysTickInstant<T> {
-        self.syst.set_reload(0x00FF_FFFF);
+    fn start(&mut self) {
+        self.syst.set_reload(MAX_RVR);
         self.syst.clear_current();
         self.syst.enable_counter();
-        SysTickInstant::now(self)
     }
 
-    pub fn stop(mut self) -> Self {
+    fn stop(&mut self) {
         self.syst.disable_counter();
-        self
     }
 
-    pub fn tick(&mut self) -> Duration {
-        self.clocks.get_syst_clock(&mut self.syst).tick()
+    fn is_running(&mut self) -> bool {
+        self.syst.is_counter_enabled()
     }
 
-    pub fn has_wrapped(&mut self) -> bool {
-        self.syst.has_wrapped()
+    /// Ticks elapsed since the counter was last (re)started, wrapping every
+    /// `MAX_RVR + 1` (`2^24`) ticks as the underlying SysTick reload wraps.
+    fn get_ticks(&mut self) -> Ticks24 {
+        Ticks24(MAX_RVR - SYST::get_current())
+    }
+}
+
+impl<T> Alarm for SysTickDelay<T>
+where
+    T: Clocks,
+{
+    /// SysTick has no compare register of its own to arm against; this just
+    /// records `(reference, dt)` for a caller to poll via
+    /// `Alarm::has_expired` against `Counter::get_ticks`, the same
+    /// software-polled model Tock OS's `hil::time::Alarm` uses on top of a
+    /// free-running counter.
+    fn set_alarm(&mut self, reference: Ticks24, dt: Ticks24) {
+        self.alarm = (reference, dt);
     }
 
-    pub fn get_current(&mut self) -> u32 {
-        SYST::get_current()
+    fn get_alarm(&self) -> (Ticks24, Ticks24) {
+        self.alarm
+    }
+
+    fn disarm(&mut self) {
+        self.alarm = (Ticks24(0), Ticks24(0));
+    }
+
+    /// One tick: the alarm is polled in software, so there is no hardware
+    /// minimum beyond the counter's own resolution.
+    fn minimum_dt(&self) -> Ticks24 {
+        Ticks24(1)
     }
 }
@@ -0,0 +1,75 @@
+//! Optional conversions to the `fugit`/`embedded-time` ecosystem.
+//!
+//! Enabled by the `fugit` cargo feature. Lets drivers written against
+//! `fugit`'s pervasive `Rate`/`Duration` APIs accept this crate's
+//! [`Frequency`] directly, without forcing the whole crate to give up its
+//! own `numerator`/`denominator`/`resolution` representation.
+
+use crate::clock::{Frequency, U32Ext};
+use core::convert::TryFrom;
+use core::time::Duration;
+use fugit::{HertzU32, MicrosDurationU64};
+
+impl From<Frequency> for HertzU32 {
+    /// Normalize through [`Frequency::into_hertz`] so the conversion is
+    /// correct regardless of the source frequency's resolution.
+    fn from(freq: Frequency) -> Self {
+        HertzU32::from_raw(freq.into_hertz().numerator)
+    }
+}
+
+impl TryFrom<HertzU32> for Frequency {
+    /// `fugit::HertzU32` has no concept of "no frequency"; a raw value of 0
+    /// does not correspond to a valid [`Frequency`].
+    type Error = ();
+
+    fn try_from(hz: HertzU32) -> Result<Self, Self::Error> {
+        match hz.raw() {
+            0 => Err(()),
+            raw => Ok(raw.hz()),
+        }
+    }
+}
+
+/// Convert a `Duration` produced by [`Frequency::tick`] or the [`U32Ext`]
+/// `ms`/`us`/`s` helpers into a `fugit` microsecond duration.
+pub fn duration_to_fugit(d: Duration) -> MicrosDurationU64 {
+    MicrosDurationU64::from_ticks(d.as_micros() as u64)
+}
+
+/// Convert a `fugit` microsecond duration back into a `core::time::Duration`.
+pub fn duration_from_fugit(d: MicrosDurationU64) -> Duration {
+    Duration::from_micros(d.ticks())
+}
+
+mod test {
+
+    #[allow(unused_imports)]
+    use super::{duration_from_fugit, duration_to_fugit, Frequency};
+    use crate::clock::U32Ext;
+    use core::convert::TryFrom;
+    use core::time::Duration;
+    use fugit::HertzU32;
+
+    #[test]
+    fn duration_round_trips_through_fugit() {
+        let d = Duration::from_micros(1_500);
+        assert_eq!(duration_from_fugit(duration_to_fugit(d)), d);
+    }
+
+    #[test]
+    fn frequency_converts_into_hertz_u32() {
+        let hz: HertzU32 = 1.khz().into();
+        assert_eq!(hz.raw(), 1_000);
+    }
+
+    #[test]
+    fn hertz_u32_converts_back_into_frequency() {
+        assert_eq!(Frequency::try_from(HertzU32::from_raw(2_000)), Ok(2.khz()));
+    }
+
+    #[test]
+    fn zero_hertz_is_not_a_valid_frequency() {
+        assert_eq!(Frequency::try_from(HertzU32::from_raw(0)), Err(()));
+    }
+}
@@ -6,3 +6,8 @@
 
 pub mod timer;
 pub mod clock;
+pub mod delay;
+pub mod monotonic;
+
+#[cfg(feature = "fugit")]
+pub mod interop;
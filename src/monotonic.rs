@@ -0,0 +1,237 @@
+//! A `Monotonic` clock built on top of [`SysTickDelay`].
+//!
+//! SysTick is only a 24-bit down-counter, so it cannot express the wide,
+//! ever-increasing instants an RTIC-style scheduler needs for `spawn_after`
+//! / `spawn_at`. [`SysTickMonotonic`] extends it into a 64-bit instant by
+//! counting wraps of the counter in a free-running overflow accumulator,
+//! incremented from the SysTick exception handler. The accumulator is 32
+//! bits wide, so instants span `2^56` ticks and wrap back to zero after that.
+
+use crate::clock::Clocks;
+use crate::delay::{SysTickDelay, MAX_RVR};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use cortex_m::peripheral::SYST;
+
+/// Number of times the SysTick counter has wrapped since the last
+/// [`SysTickMonotonic::reset`].
+///
+/// Incremented from the SysTick exception handler; `now()` re-reads it after
+/// sampling the counter and retries if it changed mid-read, so that a wrap
+/// racing a read can never make time appear to go backwards.
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+
+/// Combine an overflow count and a raw SysTick counter reading into a
+/// 64-bit instant. Pulled out of `now()` as a pure function so the packing
+/// (and its boundaries) can be unit tested without real SysTick hardware.
+fn instant_from_parts(overflows: u32, current: u32) -> u64 {
+    let elapsed_in_period = MAX_RVR - current;
+    (overflows as u64) << 24 | elapsed_in_period as u64
+}
+
+/// Clamp a remaining tick distance to a reload value the 24-bit counter can
+/// hold, never less than 1 (a reload of 0 never sets COUNTFLAG, since it
+/// only fires on a 1->0 transition). Pulled out of `rearm()` as a pure
+/// function so the clamping can be unit tested without real SysTick
+/// hardware.
+fn reload_for_remaining(remaining: u64) -> u32 {
+    core::cmp::max(core::cmp::min(remaining, MAX_RVR as u64) as u32, 1)
+}
+
+/// Equivalent of `rtic_monotonic::Monotonic`, defined locally so this crate
+/// does not have to depend on `rtic_monotonic` itself.
+pub trait Monotonic {
+    /// The instant type counted by this monotonic.
+    type Instant: Copy + Ord;
+
+    /// Return the current instant.
+    fn now(&mut self) -> Self::Instant;
+
+    /// Program a compare match to fire at `instant`.
+    fn set_compare(&mut self, instant: Self::Instant);
+
+    /// Clear the compare match condition.
+    fn clear_compare_flag(&mut self);
+
+    /// The instant corresponding to the start of time.
+    fn zero() -> Self::Instant;
+
+    /// Reset the monotonic so that `now()` starts counting from `zero()` again.
+    fn reset(&mut self);
+}
+
+/// An RTIC-style [`Monotonic`] clock backed by the Cortex-M SysTick timer,
+/// extended to a 64-bit instant spanning `2^56` ticks.
+pub struct SysTickMonotonic<T>
+where
+    T: Clocks,
+{
+    syst: SYST,
+    clocks: T,
+    compare: u64,
+}
+
+impl<T> SysTickMonotonic<T>
+where
+    T: Clocks,
+{
+    /// Build a monotonic clock from a [`SysTickDelay`], taking over the
+    /// underlying `SYST` peripheral and arming it as a free-running counter.
+    pub fn new(delay: SysTickDelay<T>) -> Self {
+        let (mut syst, clocks) = delay.free();
+        syst.set_reload(MAX_RVR);
+        syst.clear_current();
+        syst.enable_interrupt();
+        syst.enable_counter();
+        SysTickMonotonic {
+            syst,
+            clocks,
+            compare: u64::MAX,
+        }
+    }
+
+    /// Record a SysTick wrap. Call this from the SysTick exception handler.
+    pub fn on_interrupt(&mut self) {
+        OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+        // `compare` is a plain u64, not atomic like OVERFLOWS, so guard it
+        // against tearing a concurrent `set_compare`/`reset` write with a
+        // critical section instead.
+        cortex_m::interrupt::free(|_| {
+            if self.now() >= self.compare {
+                self.compare = u64::MAX;
+            } else {
+                self.rearm();
+            }
+        });
+    }
+
+    /// Re-arm the reload register for the remaining distance to `compare`,
+    /// clamped to the 24-bit counter width.
+    fn rearm(&mut self) {
+        let remaining = self.compare.saturating_sub(self.now());
+        self.syst.set_reload(reload_for_remaining(remaining));
+        self.syst.clear_current();
+    }
+
+    /// Return the duration of a single SysTick clock pulse.
+    fn tick(&mut self) -> Duration {
+        self.clocks.get_syst_clock(&mut self.syst).tick()
+    }
+}
+
+impl<T> Monotonic for SysTickMonotonic<T>
+where
+    T: Clocks,
+{
+    type Instant = u64;
+
+    fn now(&mut self) -> Self::Instant {
+        loop {
+            let overflows = OVERFLOWS.load(Ordering::Acquire);
+            let current = SYST::get_current();
+            if overflows == OVERFLOWS.load(Ordering::Acquire) {
+                return instant_from_parts(overflows, current);
+            }
+        }
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        // Called from task context, racing the SysTick ISR's own read of
+        // `compare` in `on_interrupt`/`rearm`: a u64 write is not atomic on
+        // a 32-bit core, so mask interrupts for the read-modify-write.
+        cortex_m::interrupt::free(|_| {
+            self.compare = instant;
+            self.rearm();
+        });
+    }
+
+    fn clear_compare_flag(&mut self) {
+        // SysTick auto-clears COUNTFLAG on read, nothing to do.
+    }
+
+    fn zero() -> Self::Instant {
+        0
+    }
+
+    fn reset(&mut self) {
+        cortex_m::interrupt::free(|_| {
+            OVERFLOWS.store(0, Ordering::Relaxed);
+            self.compare = u64::MAX;
+            self.syst.set_reload(MAX_RVR);
+            self.syst.clear_current();
+        });
+    }
+}
+
+/// Convert a tick count in the extended 64-bit SysTick time base into a
+/// [`Duration`], using the rate of the underlying clock.
+impl<T> SysTickMonotonic<T>
+where
+    T: Clocks,
+{
+    /// Convert a number of SysTick ticks into a [`Duration`].
+    pub fn ticks_to_duration(&mut self, ticks: u64) -> Duration {
+        Duration::from_nanos(nanos_for_ticks(self.tick().as_nanos() as u64, ticks))
+    }
+}
+
+/// Multiply a per-tick nanosecond count by a 64-bit tick count, saturating
+/// instead of wrapping. Pulled out of `ticks_to_duration` as a pure function
+/// so the saturation can be unit tested without real SysTick hardware:
+/// `ticks` routinely exceeds `u32::MAX` in this crate's 64-bit tick space
+/// (that's the whole point of [`SysTickMonotonic`]), so the multiplication
+/// must be done in 64-bit, not truncated down to `u32` first.
+fn nanos_for_ticks(tick_nanos: u64, ticks: u64) -> u64 {
+    tick_nanos.saturating_mul(ticks)
+}
+
+mod test {
+
+    #[allow(unused_imports)]
+    use super::{instant_from_parts, nanos_for_ticks, reload_for_remaining, MAX_RVR};
+
+    #[test]
+    fn instant_from_parts_tracks_a_fresh_period() {
+        // No overflows yet, counter just reloaded: elapsed is 0.
+        assert_eq!(instant_from_parts(0, MAX_RVR), 0);
+    }
+
+    #[test]
+    fn instant_from_parts_tracks_a_nearly_exhausted_period() {
+        // No overflows yet, counter about to wrap: elapsed is MAX_RVR.
+        assert_eq!(instant_from_parts(0, 0), MAX_RVR as u64);
+    }
+
+    #[test]
+    fn instant_from_parts_folds_overflows_into_the_high_bits() {
+        assert_eq!(instant_from_parts(1, MAX_RVR), 1u64 << 24);
+        assert_eq!(instant_from_parts(2, 0), (2u64 << 24) | MAX_RVR as u64);
+    }
+
+    #[test]
+    fn reload_for_remaining_clamps_to_the_24_bit_counter_width() {
+        assert_eq!(reload_for_remaining(MAX_RVR as u64 + 1_000), MAX_RVR);
+        assert_eq!(reload_for_remaining(MAX_RVR as u64), MAX_RVR);
+    }
+
+    #[test]
+    fn reload_for_remaining_never_reloads_zero() {
+        // A reload of 0 never sets COUNTFLAG (it only fires on a 1->0
+        // transition), so an already-elapsed compare must still reload 1.
+        assert_eq!(reload_for_remaining(0), 1);
+    }
+
+    #[test]
+    fn nanos_for_ticks_does_not_truncate_past_u32_max() {
+        // At a tick period of 1000ns (1MHz), u32::MAX ticks alone already
+        // overflows a naive `tick * (ticks as u32)`; a 64-bit tick count
+        // well beyond u32::MAX must still multiply correctly.
+        let ticks = (u32::MAX as u64) * 4;
+        assert_eq!(nanos_for_ticks(1_000, ticks), ticks * 1_000);
+    }
+
+    #[test]
+    fn nanos_for_ticks_saturates_instead_of_wrapping() {
+        assert_eq!(nanos_for_ticks(u64::MAX, 2), u64::MAX);
+    }
+}
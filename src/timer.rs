@@ -5,6 +5,15 @@ use void::Void;
 use core::time::Duration;
 use core::ops::Sub;
 use core::convert::Into;
+use embedded_hal::timer::{Cancel, CountDown, Periodic};
+
+/// Error produced while configuring a timer peripheral.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerError {
+    /// The requested duration cannot be realized by any prescaler/reload
+    /// pair the peripheral supports.
+    Unreachable,
+}
 
 /// Tell if subtractions overflow.
 pub trait CheckedSub {
@@ -70,6 +79,14 @@ where
 
     /// Return the duration between 2 counted value.
     fn tick(&mut self) -> Duration;
+
+    /// Program the counter so that a subsequent `start()` wraps after
+    /// approximately `d`, clamped to whatever a single counting period can
+    /// hold (see `limit_value`). Used to realize an arbitrary duration
+    /// through [`CountDownTimer`], chunking a `d` wider than one period
+    /// over successive re-armed windows the same way `SysTickDelay::delay`
+    /// chunks a blocking delay over multiple reload windows.
+    fn set_period(&mut self, d: Duration);
 }
 
 
@@ -109,4 +126,418 @@ where T : Timer
     pub fn stop(self) -> T {
         self.delay.stop()
     }
+
+    /// Test if the counter has wrapped to its initial value.
+    pub fn has_wrapped(&mut self) -> bool {
+        self.delay.has_wrapped()
+    }
+}
+
+/// Tell how to perform wrapping subtraction.
+pub trait WrappingSub {
+    /// Subtract `rhs`, wrapping around at the type's maximum value instead
+    /// of overflowing.
+    fn wrapping_sub_impl(self, rhs: Self) -> Self;
+}
+
+impl WrappingSub for u32 {
+    fn wrapping_sub_impl(self, rhs: u32) -> u32 {
+        self.wrapping_sub(rhs)
+    }
+}
+
+/// A tick count that wraps at `2^24` instead of at its storage type's own
+/// (32-bit) width.
+///
+/// `Counter::get_ticks`/`Alarm`'s wrapping arithmetic are only correct if
+/// `wrapping_sub_impl`'s modulus matches the width the underlying counter
+/// actually wraps at; a plain `u32` assumes a full 32-bit wrap, which is
+/// wrong for a narrower hardware counter (e.g. SysTick's 24 bits) padded
+/// into a `u32`. `Ticks24` carries the right modulus so a `Counter`/`Alarm`
+/// built on such a counter can't get this wrong by mistake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ticks24(pub u32);
+
+impl WrappingSub for Ticks24 {
+    fn wrapping_sub_impl(self, rhs: Self) -> Self {
+        const MODULUS: u32 = 1 << 24;
+        Ticks24(self.0.wrapping_sub(rhs.0) & (MODULUS - 1))
+    }
+}
+
+/// A free-running counter, in the style of Tock OS's `hil::time::Counter`.
+pub trait Counter {
+    /// Inner type of the counter.
+    type U: WrappingSub + PartialOrd + Copy;
+
+    /// Start the counter running.
+    fn start(&mut self);
+
+    /// Stop the counter.
+    fn stop(&mut self);
+
+    /// Tell if the counter is currently running.
+    ///
+    /// `&mut self`, not `&self`: on Cortex-M, reading whether SysTick is
+    /// enabled goes through `SYST::is_counter_enabled`, which itself takes
+    /// `&mut self` (matching the `&mut self` precedent already used by
+    /// `get_ticks`).
+    fn is_running(&mut self) -> bool;
+
+    /// Return the current tick count. This wraps around at the counter's
+    /// width, so callers should only ever compare two reads with wrapping
+    /// arithmetic, never plain subtraction.
+    fn get_ticks(&mut self) -> Self::U;
+}
+
+/// Receives the callback fired when an armed [`Alarm`] expires.
+pub trait AlarmClient {
+    /// Called from the timer ISR when the armed alarm expires.
+    fn alarm(&mut self);
+}
+
+/// A one-shot/periodic alarm built on top of a [`Counter`], in the style of
+/// Tock OS's `hil::time::Alarm`.
+pub trait Alarm: Counter {
+    /// Arm the alarm to fire once `now.wrapping_sub(reference) >= dt`. This
+    /// fire condition, rather than a plain target value, is what lets the
+    /// alarm survive a counter wraparound between `reference` and now.
+    fn set_alarm(&mut self, reference: Self::U, dt: Self::U);
+
+    /// Return the `(reference, dt)` pair of the currently armed alarm.
+    fn get_alarm(&self) -> (Self::U, Self::U);
+
+    /// Disarm the alarm.
+    fn disarm(&mut self);
+
+    /// The smallest `dt` that is guaranteed not to be missed. Requests
+    /// closer to `now` than this should be bumped forward to it so the
+    /// alarm cannot be armed for an instant that has already passed by the
+    /// time the peripheral is programmed.
+    fn minimum_dt(&self) -> Self::U;
+
+    /// Tell whether `now` has reached the alarm's fire condition.
+    fn has_expired(&self, now: Self::U) -> bool {
+        let (reference, dt) = self.get_alarm();
+        now.wrapping_sub_impl(reference) >= dt
+    }
+}
+
+/// Error produced by [`Cancel`] when [`CountDownTimer::cancel`] is called
+/// while the timer is not counting down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountDownError {
+    /// `cancel` was called while the timer was not running.
+    NotRunning,
+}
+
+enum CountDownState<T>
+where
+    T: Timer,
+{
+    Idle(T),
+    Counting(TimerInstant<T>),
+}
+
+/// Adapt any [`Timer`] to the standard `embedded_hal::timer::{CountDown,
+/// Periodic, Cancel}` traits, so generic `embedded-hal` drivers can consume
+/// it directly.
+pub struct CountDownTimer<T>
+where
+    T: Timer,
+{
+    state: Option<CountDownState<T>>,
+    /// Time still owed across the remaining re-armed windows.
+    remaining: Duration,
+    /// Duration requested by the last `start`, reused to auto-reload for
+    /// `Periodic`.
+    period: Duration,
+    /// The window actually armed via `set_period` for the counting state
+    /// currently held in `state`, i.e. what one more wrap is worth.
+    window: Duration,
+}
+
+impl<T> CountDownTimer<T>
+where
+    T: Timer,
+{
+    /// Wrap `timer` for use through the `embedded_hal` timer traits.
+    pub fn new(timer: T) -> Self {
+        CountDownTimer {
+            state: Some(CountDownState::Idle(timer)),
+            remaining: Duration::from_secs(0),
+            period: Duration::from_secs(0),
+            window: Duration::from_secs(0),
+        }
+    }
+
+    fn take_timer(&mut self) -> T {
+        match self.state.take().expect("CountDownTimer state poisoned") {
+            CountDownState::Idle(timer) => timer,
+            CountDownState::Counting(instant) => instant.stop(),
+        }
+    }
+
+    /// Program `timer` via `set_period` to cover as much of `target` as a
+    /// single counting period can hold, clamped to `limit_value`, and
+    /// remember the window actually armed so `wait` knows how much of
+    /// `remaining` one more wrap is worth.
+    fn arm(&mut self, timer: &mut T, target: Duration) {
+        let full_period = timer.tick() * timer.limit_value().into();
+        let window = core::cmp::min(target, full_period);
+        timer.set_period(window);
+        self.window = window;
+    }
+}
+
+impl<T> CountDown for CountDownTimer<T>
+where
+    T: Timer,
+{
+    type Time = Duration;
+
+    /// Program the counter using `ticks_in(count)` (via `Timer::set_period`),
+    /// chunking `count` over successive reload windows when it exceeds what
+    /// a single counting period can hold, the same way `SysTickDelay::delay`
+    /// chunks a blocking delay over multiple reload windows.
+    fn start<D>(&mut self, count: D)
+    where
+        D: Into<Duration>,
+    {
+        let count = count.into();
+        self.period = count;
+        self.remaining = count;
+        let mut timer = self.take_timer();
+        self.arm(&mut timer, count);
+        self.state = Some(CountDownState::Counting(timer.start()));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.state.as_mut() {
+            // `has_wrapped` takes `&mut self`, so it cannot be called from
+            // inside a match guard (the scrutinee is only borrowed
+            // immutably there); test it in the arm body instead.
+            Some(CountDownState::Counting(instant)) => {
+                if !instant.has_wrapped() {
+                    return Err(nb::Error::WouldBlock);
+                }
+            }
+            _ => panic!("CountDown::wait called before CountDown::start"),
+        }
+
+        self.remaining = self.remaining.saturating_sub(self.window);
+        let mut timer = self.take_timer();
+
+        if self.remaining.is_zero() {
+            // Auto-reload the last requested period: every implementor of
+            // this adapter also satisfies `Periodic`.
+            self.remaining = self.period;
+            self.arm(&mut timer, self.period);
+            self.state = Some(CountDownState::Counting(timer.start()));
+            Ok(())
+        } else {
+            let remaining = self.remaining;
+            self.arm(&mut timer, remaining);
+            self.state = Some(CountDownState::Counting(timer.start()));
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<T> Periodic for CountDownTimer<T> where T: Timer {}
+
+impl<T> Cancel for CountDownTimer<T>
+where
+    T: Timer,
+{
+    type Error = CountDownError;
+
+    fn cancel(&mut self) -> Result<(), Self::Error> {
+        match self.state.take().expect("CountDownTimer state poisoned") {
+            CountDownState::Counting(instant) => {
+                self.state = Some(CountDownState::Idle(instant.stop()));
+                Ok(())
+            }
+            idle @ CountDownState::Idle(_) => {
+                self.state = Some(idle);
+                Err(CountDownError::NotRunning)
+            }
+        }
+    }
+}
+
+mod test {
+
+    #[allow(unused_imports)]
+    use super::{Alarm, Counter, CountDownError, CountDownTimer, Ticks24, Timer, TimerInstant};
+    use core::time::Duration;
+    use embedded_hal::timer::{Cancel, CountDown};
+    use void::Void;
+
+    /// A software `Alarm`/`Counter` whose state is just the last-armed
+    /// `(reference, dt)` pair, so `Alarm::has_expired`'s default wrapping
+    /// arithmetic can be exercised without real SysTick hardware.
+    struct FakeAlarm {
+        alarm: (Ticks24, Ticks24),
+    }
+
+    impl Counter for FakeAlarm {
+        type U = Ticks24;
+
+        fn start(&mut self) {}
+        fn stop(&mut self) {}
+        fn is_running(&mut self) -> bool {
+            true
+        }
+        fn get_ticks(&mut self) -> Ticks24 {
+            self.alarm.0
+        }
+    }
+
+    impl Alarm for FakeAlarm {
+        fn set_alarm(&mut self, reference: Ticks24, dt: Ticks24) {
+            self.alarm = (reference, dt);
+        }
+
+        fn get_alarm(&self) -> (Ticks24, Ticks24) {
+            self.alarm
+        }
+
+        fn disarm(&mut self) {
+            self.alarm = (Ticks24(0), Ticks24(0));
+        }
+
+        fn minimum_dt(&self) -> Ticks24 {
+            Ticks24(1)
+        }
+    }
+
+    #[test]
+    fn has_expired_is_false_before_dt_elapses() {
+        let mut alarm = FakeAlarm {
+            alarm: (Ticks24(0), Ticks24(0)),
+        };
+        alarm.set_alarm(Ticks24(100), Ticks24(50));
+        assert!(!alarm.has_expired(Ticks24(120)));
+        assert!(alarm.has_expired(Ticks24(150)));
+    }
+
+    #[test]
+    fn has_expired_survives_a_wraparound_between_reference_and_now() {
+        // SysTick's 24-bit counter wraps well before `now` would overflow a
+        // `u32`; `has_expired` must still recognize `dt` has elapsed once
+        // `now` has wrapped past the reference.
+        const MODULUS: u32 = 1 << 24;
+        let mut alarm = FakeAlarm {
+            alarm: (Ticks24(0), Ticks24(0)),
+        };
+        alarm.set_alarm(Ticks24(MODULUS - 10), Ticks24(20));
+
+        // Only 5 ticks have elapsed so far (no wrap yet).
+        assert!(!alarm.has_expired(Ticks24(MODULUS - 5)));
+        // `now` has wrapped past 0; 25 ticks have elapsed since reference.
+        assert!(alarm.has_expired(Ticks24(15)));
+    }
+
+    /// A software `Timer` whose "hardware" is a single down-counter that
+    /// advances by one tick on every poll of `has_wrapped`, so tests can
+    /// drive it deterministically without real time passing.
+    struct FakeTimer {
+        ticks_per_second: u32,
+        limit: u32,
+        remaining: u32,
+    }
+
+    impl FakeTimer {
+        fn new(ticks_per_second: u32, limit: u32) -> Self {
+            FakeTimer {
+                ticks_per_second,
+                limit,
+                remaining: limit,
+            }
+        }
+    }
+
+    impl Timer for FakeTimer {
+        type U = u32;
+
+        fn delay(&mut self, _d: Duration) {}
+
+        fn wait(&mut self, _d: Duration) -> nb::Result<(), Void> {
+            Err(nb::Error::WouldBlock)
+        }
+
+        fn start(self) -> TimerInstant<Self> {
+            TimerInstant::now(self)
+        }
+
+        fn stop(self) -> Self {
+            self
+        }
+
+        fn has_wrapped(&mut self) -> bool {
+            // Decrement first, then test for zero: real SysTick counts down
+            // to 0 and reports COUNTFLAG on that very poll, so a window of
+            // N ticks wraps on the Nth poll, not the (N+1)th.
+            if self.remaining == 0 {
+                self.remaining = self.limit;
+            }
+            self.remaining -= 1;
+            self.remaining == 0
+        }
+
+        fn limit_value(&self) -> u32 {
+            self.limit
+        }
+
+        fn get_current(&mut self) -> u32 {
+            self.remaining
+        }
+
+        fn tick(&mut self) -> Duration {
+            Duration::from_nanos(1_000_000_000 / self.ticks_per_second as u64)
+        }
+
+        fn set_period(&mut self, d: Duration) {
+            let ticks = (d.as_nanos() as u64 * self.ticks_per_second as u64 / 1_000_000_000) as u32;
+            self.limit = ticks;
+            self.remaining = ticks;
+        }
+    }
+
+    #[test]
+    fn count_down_fires_after_the_requested_window_not_the_native_period() {
+        // A 1 kHz counter whose native period is a full second.
+        let timer = FakeTimer::new(1_000, 1_000);
+        let mut count_down = CountDownTimer::new(timer);
+        count_down.start(Duration::from_millis(10));
+
+        for _ in 0..9 {
+            assert_eq!(count_down.wait(), Err(nb::Error::WouldBlock));
+        }
+        assert_eq!(count_down.wait(), Ok(()));
+    }
+
+    #[test]
+    fn count_down_chunks_a_duration_wider_than_one_period() {
+        // Native period is only 10 ticks; request 25 ticks worth.
+        let timer = FakeTimer::new(1_000, 10);
+        let mut count_down = CountDownTimer::new(timer);
+        count_down.start(Duration::from_millis(25));
+
+        for _ in 0..24 {
+            assert_eq!(count_down.wait(), Err(nb::Error::WouldBlock));
+        }
+        assert_eq!(count_down.wait(), Ok(()));
+    }
+
+    #[test]
+    fn cancel_stops_a_running_count_down() {
+        let timer = FakeTimer::new(1_000, 1_000);
+        let mut count_down = CountDownTimer::new(timer);
+        count_down.start(Duration::from_millis(10));
+
+        assert_eq!(count_down.cancel(), Ok(()));
+        assert_eq!(count_down.cancel(), Err(CountDownError::NotRunning));
+    }
 }